@@ -49,8 +49,10 @@ fn main() {
   spawn(proc() {
     let mut udpStream = readSocket.connect(remoteAddr);
     loop {
-      let msg = OscMessage::from_reader(&mut udpStream).unwrap();
-      println!("recv {}: {:?}", msg.address, msg.arguments);
+      match OscMessage::from_reader(&mut udpStream) {
+        Ok(msg) => println!("recv {}: {:?}", msg.address, msg.arguments),
+        Err(err) => println!("dropping malformed packet: {:?}", err)
+      }
     }
   });
 
@@ -1,25 +1,272 @@
 #[feature(macro_rules)];
+#[cfg_attr(not(feature = "std"), no_std)];
 
+#[cfg(not(feature = "std"))]
+extern crate core;
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+use std::char;
+#[cfg(feature = "std")]
 use std::str;
-use std::io;
-use std::vec;
-use std::io::{Writer, IoResult, Reader, InvalidInput};
+#[cfg(feature = "std")]
 use std::result::{Err, Ok};
 
-macro_rules! unwrap_return_err(
+#[cfg(not(feature = "std"))]
+use core::char;
+#[cfg(not(feature = "std"))]
+use core::str;
+#[cfg(not(feature = "std"))]
+use core::result::{Err, Ok};
+
+#[cfg(feature = "std")]
+use std::io::{Writer, IoError, Reader, EndOfFile};
+
+#[cfg(feature = "std")]
+use std::cast::transmute;
+
+#[cfg(not(feature = "std"))]
+use core::mem::transmute;
+
+macro_rules! try_io(
   ($inp:expr) => (
     match $inp {
-      Err(res) => return Err(res),
+      Err(err) => return Err(io_to_osc(err)),
       Ok(a) => a
     }
   );
 )
 
+macro_rules! try_osc(
+  ($inp:expr) => (
+    match $inp {
+      Err(err) => return Err(err),
+      Ok(a) => a
+    }
+  );
+)
+
+/// Errors produced while decoding or encoding OSC packets.
+///
+/// A malformed packet should never be able to panic the reader: every
+/// failure mode that can arise from untrusted bytes on the wire is
+/// represented here instead of being collapsed into a generic io error
+/// or a `fail!`.
+#[deriving(Show)]
+pub enum OscError {
+  #[cfg(feature = "std")]
+  Io(IoError),
+  UnknownTypeTag(char),
+  MalformedAddress,
+  MissingTypeTagString,
+  InvalidUtf8,
+  TruncatedPacket
+}
+
+pub type OscResult<T> = Result<T, OscError>;
+
+#[cfg(feature = "std")]
+#[inline]
+fn io_to_osc(err: IoError) -> OscError {
+  match err.kind {
+    EndOfFile => TruncatedPacket,
+    _ => Io(err)
+  }
+}
+
+// Minimal byte-oriented I/O abstraction, in the style of the `core_io`
+// crates: the codec below (OscType, OscWriter, OscReader, OscMessage,
+// ...) is written against these two traits instead of `std::io`, so it
+// has no hard dependency on `std` and can run in a `#![no_std]` firmware
+// context given a global allocator for the `~str`/`~[u8]` owned types.
+// With the default `std` feature enabled (see the blanket impls below),
+// any `std::io::Reader`/`Writer` already satisfies these for free.
+pub trait OscRead {
+  fn read_u8(&mut self) -> OscResult<u8>;
+  fn read_bytes(&mut self, len: uint) -> OscResult<~[u8]>;
+}
+
+pub trait OscWrite {
+  fn write_bytes(&mut self, buf: &[u8]) -> OscResult<()>;
+}
+
+#[cfg(feature = "std")]
+impl<R: Reader> OscRead for R {
+  fn read_u8(&mut self) -> OscResult<u8> {
+    match self.read_byte() {
+      Ok(b) => Ok(b),
+      Err(err) => Err(io_to_osc(err))
+    }
+  }
+
+  fn read_bytes(&mut self, len: uint) -> OscResult<~[u8]> {
+    match Reader::read_bytes(self, len) {
+      Ok(bytes) => Ok(bytes),
+      Err(err) => Err(io_to_osc(err))
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<W: Writer> OscWrite for W {
+  fn write_bytes(&mut self, buf: &[u8]) -> OscResult<()> {
+    match Writer::write(self, buf) {
+      Ok(_) => Ok(()),
+      Err(err) => Err(io_to_osc(err))
+    }
+  }
+}
+
+/// A growable byte buffer that implements `OscWrite` without going
+/// through `std::io::MemWriter` -- the `alloc`-only path for callers
+/// (embedded targets included) who just want the encoded bytes of a
+/// message/packet in a caller-owned buffer.
+pub struct OscBuf {
+  priv bytes: ~[u8]
+}
+
+impl OscBuf {
+  pub fn new() -> OscBuf {
+    OscBuf { bytes: ~[] }
+  }
+
+  pub fn unwrap(self) -> ~[u8] {
+    self.bytes
+  }
+}
+
+impl OscWrite for OscBuf {
+  fn write_bytes(&mut self, buf: &[u8]) -> OscResult<()> {
+    self.bytes.push_all(buf);
+    Ok(())
+  }
+}
+
+/// Reads from a caller-supplied byte slice, implementing `OscRead`
+/// without `std::io::MemReader` -- the no-`std` counterpart to `OscBuf`.
+pub struct OscSliceReader<'a> {
+  priv bytes: &'a [u8],
+  priv pos: uint
+}
+
+impl<'a> OscSliceReader<'a> {
+  pub fn new<'a>(bytes: &'a [u8]) -> OscSliceReader<'a> {
+    OscSliceReader { bytes: bytes, pos: 0 }
+  }
+}
+
+impl<'a> OscRead for OscSliceReader<'a> {
+  fn read_u8(&mut self) -> OscResult<u8> {
+    if self.pos >= self.bytes.len() {
+      return Err(TruncatedPacket);
+    }
+    let b = self.bytes[self.pos];
+    self.pos += 1;
+    Ok(b)
+  }
+
+  fn read_bytes(&mut self, len: uint) -> OscResult<~[u8]> {
+    if self.pos + len > self.bytes.len() {
+      return Err(TruncatedPacket);
+    }
+    let slice = self.bytes.slice(self.pos, self.pos + len);
+    self.pos += len;
+    Ok(slice.to_owned())
+  }
+}
+
+// Big-endian numeric helpers built only on top of OscRead::read_u8 /
+// OscWrite::write_bytes, since those are the only primitives every
+// implementor is guaranteed to have (std's Reader/Writer convenience
+// methods like read_be_i32 aren't available once the codec is generic).
+fn read_be_u32<R: OscRead>(re: &mut R) -> OscResult<u32> {
+  let a = try_osc!(re.read_u8()) as u32;
+  let b = try_osc!(re.read_u8()) as u32;
+  let c = try_osc!(re.read_u8()) as u32;
+  let d = try_osc!(re.read_u8()) as u32;
+  Ok((a << 24) | (b << 16) | (c << 8) | d)
+}
+
+fn read_be_u64<R: OscRead>(re: &mut R) -> OscResult<u64> {
+  let hi = try_osc!(read_be_u32(re)) as u64;
+  let lo = try_osc!(read_be_u32(re)) as u64;
+  Ok((hi << 32) | lo)
+}
+
+fn read_be_i32<R: OscRead>(re: &mut R) -> OscResult<i32> {
+  Ok(try_osc!(read_be_u32(re)) as i32)
+}
+
+fn read_be_i64<R: OscRead>(re: &mut R) -> OscResult<i64> {
+  Ok(try_osc!(read_be_u64(re)) as i64)
+}
+
+fn read_be_f32<R: OscRead>(re: &mut R) -> OscResult<f32> {
+  let bits = try_osc!(read_be_u32(re));
+  Ok(unsafe { transmute(bits) })
+}
+
+fn read_be_f64<R: OscRead>(re: &mut R) -> OscResult<f64> {
+  let bits = try_osc!(read_be_u64(re));
+  Ok(unsafe { transmute(bits) })
+}
+
+fn write_be_u32<W: OscWrite>(wr: &mut W, val: u32) -> OscResult<()> {
+  wr.write_bytes([(val >> 24) as u8, (val >> 16) as u8, (val >> 8) as u8, val as u8])
+}
+
+fn write_be_u64<W: OscWrite>(wr: &mut W, val: u64) -> OscResult<()> {
+  try_osc!(write_be_u32(wr, (val >> 32) as u32));
+  write_be_u32(wr, val as u32)
+}
+
+fn write_be_i32<W: OscWrite>(wr: &mut W, val: i32) -> OscResult<()> {
+  write_be_u32(wr, val as u32)
+}
+
+fn write_be_i64<W: OscWrite>(wr: &mut W, val: i64) -> OscResult<()> {
+  write_be_u64(wr, val as u64)
+}
+
+fn write_be_f32<W: OscWrite>(wr: &mut W, val: f32) -> OscResult<()> {
+  write_be_u32(wr, unsafe { transmute(val) })
+}
+
+fn write_be_f64<W: OscWrite>(wr: &mut W, val: f64) -> OscResult<()> {
+  write_be_u64(wr, unsafe { transmute(val) })
+}
+
+// Validates `bytes` as UTF-8 and reinterprets it as an owned string in
+// place. `core::str` has no owned-string conversion (core has no
+// allocator), so this stands in for `std::str::from_utf8_owned` on both
+// the std and no_std paths.
+fn owned_utf8(bytes: ~[u8]) -> Option<~str> {
+  if str::from_utf8(bytes).is_some() {
+    Some(unsafe { transmute(bytes) })
+  } else {
+    None()
+  }
+}
+
 pub enum OscType {
   OscString(~str),
   OscInt(i32),
   OscFloat(f32),
-  OscBlob(~[u8])
+  OscBlob(~[u8]),
+  OscInt64(i64),
+  OscDouble(f64),
+  OscTimeTag(OscTimeTag),
+  OscChar(char),
+  OscColor(u8, u8, u8, u8),
+  OscMidi(u8, u8, u8, u8),
+  // 'T', 'F', 'N', 'I' carry no bytes in the argument section; they
+  // exist only as a tag in the typetag string.
+  OscTrue,
+  OscFalse,
+  OscNil,
+  OscInfinitum
 }
 
 impl OscType {
@@ -54,6 +301,54 @@ impl OscType {
       _ => fail!("attempt to unwrap a not float as a float")
     }
   }
+
+  #[inline]
+  pub fn unwrap_int64(self) -> i64 {
+    match self {
+      OscInt64(val) => val,
+      _ => fail!("attempt to unwrap a not int64 as an int64")
+    }
+  }
+
+  #[inline]
+  pub fn unwrap_double(self) -> f64 {
+    match self {
+      OscDouble(val) => val,
+      _ => fail!("attempt to unwrap a not double as a double")
+    }
+  }
+
+  #[inline]
+  pub fn unwrap_timetag(self) -> OscTimeTag {
+    match self {
+      OscTimeTag(val) => val,
+      _ => fail!("attempt to unwrap a not timetag as a timetag")
+    }
+  }
+
+  #[inline]
+  pub fn unwrap_char(self) -> char {
+    match self {
+      OscChar(val) => val,
+      _ => fail!("attempt to unwrap a not char as a char")
+    }
+  }
+
+  #[inline]
+  pub fn unwrap_color(self) -> (u8, u8, u8, u8) {
+    match self {
+      OscColor(r, g, b, a) => (r, g, b, a),
+      _ => fail!("attempt to unwrap a not color as a color")
+    }
+  }
+
+  #[inline]
+  pub fn unwrap_midi(self) -> (u8, u8, u8, u8) {
+    match self {
+      OscMidi(port, status, data1, data2) => (port, status, data1, data2),
+      _ => fail!("attempt to unwrap a not midi message as a midi message")
+    }
+  }
 }
 // using traits to define methods on base traits
 // using enums to denote types instead of implementing the types
@@ -71,26 +366,34 @@ impl OscType {
 //  OscWriter::new(writer).write(stuff):
 //    type checking, possible ickiness
 
-pub struct OscWriter<'a> {
-  priv wr: &'a mut Writer
+pub struct OscWriter<'a, W> {
+  priv wr: &'a mut W
 }
 
-impl <'a> OscWriter<'a> {
-  fn new<'a>(wr: &'a mut Writer) -> OscWriter<'a> {
+impl <'a, W: OscWrite> OscWriter<'a, W> {
+  fn new<'a>(wr: &'a mut W) -> OscWriter<'a, W> {
     OscWriter {wr: wr}
   }
 
-  pub fn write(&mut self, osc: &OscType) -> IoResult<()> {
+  pub fn write(&mut self, osc: &OscType) -> OscResult<()> {
     match osc {
       &OscString(ref val) => self.write_osc_string(val),
       &OscInt(val) => self.write_osc_int(val),
       &OscFloat(val) => self.write_osc_float(val),
-      &OscBlob(ref val) => self.write_osc_blob(val)
+      &OscBlob(ref val) => self.write_osc_blob(val),
+      &OscInt64(val) => write_be_i64(self.wr, val),
+      &OscDouble(val) => write_be_f64(self.wr, val),
+      &OscTimeTag(ref val) => val.write_to(self.wr),
+      &OscChar(val) => write_be_u32(self.wr, val as u32),
+      &OscColor(r, g, b, a) => self.wr.write_bytes([r, g, b, a]),
+      &OscMidi(port, status, data1, data2) => self.wr.write_bytes([port, status, data1, data2]),
+      // no payload -- the typetag alone carries the value
+      &OscTrue | &OscFalse | &OscNil | &OscInfinitum => Ok(())
     }
   }
 
 
-  fn write_osc_string(&mut self, oscStr: &~str) -> IoResult<()> {
+  fn write_osc_string(&mut self, oscStr: &~str) -> OscResult<()> {
     let mut len = oscStr.len() + 1;
     let instr_len = oscStr.len();
     if len & 3 != 0 {
@@ -98,66 +401,84 @@ impl <'a> OscWriter<'a> {
       len = (len|3) + 1;
     }
 
-    for b in oscStr.bytes() {
-      unwrap_return_err!(self.wr.write_u8(b));
-    }
-
+    let mut buf: ~[u8] = ~[];
+    buf.push_all(oscStr.as_bytes());
     for _ in range(instr_len, len) {
-      unwrap_return_err!(self.wr.write_u8(0 as u8));
+      buf.push(0u8);
     }
 
-    Ok(())
+    self.wr.write_bytes(buf)
   }
 
-  fn write_osc_blob(&mut self, blob: &~[u8]) -> IoResult<()> {
-    let size = blob.len();
-    match self.wr.write_be_i32(size as i32) {
-      Ok(_) => {},
-      e => return e
+  fn write_osc_blob(&mut self, blob: &~[u8]) -> OscResult<()> {
+    try_osc!(write_be_i32(self.wr, blob.len() as i32));
+
+    let mut len = blob.len();
+    if len & 3 != 0 {
+      len = (len|3) + 1;
     }
-    self.wr.write(*blob)
+
+    let mut buf: ~[u8] = ~[];
+    buf.push_all(*blob);
+    for _ in range(blob.len(), len) {
+      buf.push(0u8);
+    }
+
+    self.wr.write_bytes(buf)
   }
 
-  fn write_osc_int(&mut self, oscInt: i32) -> IoResult<()> {
-    return self.wr.write_be_i32(oscInt);
+  fn write_osc_int(&mut self, oscInt: i32) -> OscResult<()> {
+    write_be_i32(self.wr, oscInt)
   }
 
-  fn write_osc_float(&mut self, oscFloat: f32) -> IoResult<()> {
-    return self.wr.write_be_f32(oscFloat);
+  fn write_osc_float(&mut self, oscFloat: f32) -> OscResult<()> {
+    write_be_f32(self.wr, oscFloat)
   }
 }
 
-pub struct OscReader<'a> {
-  priv re: &'a mut Reader
+// A blob length comes straight off the wire, so without a cap a single
+// malformed datagram (e.g. typetag ",b" with length 0x7fffffff) would
+// make read_bytes try to allocate gigabytes and abort the listener --
+// far beyond any blob a real OSC peer would ever send.
+static MAX_BLOB_LEN: i32 = 1 << 24;
+
+pub struct OscReader<'a, R> {
+  priv re: &'a mut R
 }
 
-impl<'a> OscReader<'a> {
-  pub fn new<'a>(reader: &'a mut Reader) -> OscReader<'a> {
+impl<'a, R: OscRead> OscReader<'a, R> {
+  pub fn new<'a>(reader: &'a mut R) -> OscReader<'a, R> {
     OscReader {re: reader}
   }
 
-  pub fn read(&mut self, typetag: char) -> IoResult<OscType> {
+  pub fn read(&mut self, typetag: char) -> OscResult<OscType> {
     match typetag {
       's' => self.read_osc_string(),
       'i' => self.read_osc_int(),
       'f' => self.read_osc_float(),
       'b' => self.read_osc_blob(),
-      _   => Err(io::standard_error(InvalidInput))
+      'h' => self.read_osc_int64(),
+      'd' => self.read_osc_double(),
+      't' => self.read_osc_timetag(),
+      'c' => self.read_osc_char(),
+      'r' => self.read_osc_color(),
+      'm' => self.read_osc_midi(),
+      // no payload -- the typetag alone carries the value
+      'T' => Ok(OscTrue),
+      'F' => Ok(OscFalse),
+      'N' => Ok(OscNil),
+      'I' => Ok(OscInfinitum),
+      _   => Err(UnknownTypeTag(typetag))
     }
   }
 
-  fn read_osc_string(&mut self) -> IoResult<OscType> {
+  fn read_osc_string(&mut self) -> OscResult<OscType> {
     let mut str_bytes : ~[u8] = ~[];
 
     loop {
-      match self.re.read_byte() {
-        Ok(0u8) => {
-          break;
-        },
-        Ok(b) => {
-          str_bytes.push(b);
-        },
-        Err(err) => return Err(err)
+      match try_osc!(self.re.read_u8()) {
+        0u8 => break,
+        b => str_bytes.push(b)
       }
     }
 
@@ -167,53 +488,127 @@ impl<'a> OscReader<'a> {
     }
 
     for _ in range(str_bytes.len()+1, len) {
-      match self.re.read_byte() {
-        Err(err) => return Err(err),
-        _ => {}
-      }
+      try_osc!(self.re.read_u8());
     }
 
-    return match str::from_utf8_owned(str_bytes) {
+    return match owned_utf8(str_bytes) {
       Some(val) => Ok(OscString(val)),
-      None() => Err(io::standard_error(InvalidInput))
+      None() => Err(InvalidUtf8)
     };
   }
 
 
-  fn read_osc_blob(&mut self) -> IoResult<OscType> {
-    let len = match self.re.read_be_i32() {
-      Ok(len) => len,
-      Err(err) => return Err(err)
-    };
+  fn read_osc_blob(&mut self) -> OscResult<OscType> {
+    let len = try_osc!(read_be_i32(self.re));
+    if len < 0 || len > MAX_BLOB_LEN {
+      return Err(TruncatedPacket);
+    }
 
-    return match self.re.read_bytes(len as uint) {
-      Ok(bytes) => Ok(OscBlob(bytes)),
-      Err(err) => Err(err)
-    };
-  }
+    let bytes = try_osc!(self.re.read_bytes(len as uint));
 
-  fn read_osc_int(&mut self) -> IoResult<OscType> {
-    match self.re.read_be_i32() {
-      Ok(val) => Ok(OscInt(val)),
-      Err(err) => Err(err)
+    let mut padded_len = bytes.len();
+    if padded_len & 3 != 0 {
+      padded_len = (padded_len|3) + 1;
     }
+    for _ in range(bytes.len(), padded_len) {
+      try_osc!(self.re.read_u8());
+    }
+
+    Ok(OscBlob(bytes))
+  }
+
+  fn read_osc_int(&mut self) -> OscResult<OscType> {
+    let val = try_osc!(read_be_i32(self.re));
+    Ok(OscInt(val))
+  }
+
+  fn read_osc_float(&mut self) -> OscResult<OscType> {
+    let val = try_osc!(read_be_f32(self.re));
+    Ok(OscFloat(val))
+  }
+
+  fn read_osc_int64(&mut self) -> OscResult<OscType> {
+    let val = try_osc!(read_be_i64(self.re));
+    Ok(OscInt64(val))
+  }
+
+  fn read_osc_double(&mut self) -> OscResult<OscType> {
+    let val = try_osc!(read_be_f64(self.re));
+    Ok(OscDouble(val))
+  }
+
+  fn read_osc_timetag(&mut self) -> OscResult<OscType> {
+    let timetag = try_osc!(OscTimeTag::from_reader(self.re));
+    Ok(OscTimeTag(timetag))
   }
 
-  fn read_osc_float(&mut self) -> IoResult<OscType> {
-    match self.re.read_be_f32() {
-      Ok(val) => Ok(OscFloat(val)),
-      Err(err) => Err(err)
+  fn read_osc_char(&mut self) -> OscResult<OscType> {
+    let val = try_osc!(read_be_u32(self.re));
+    match char::from_u32(val) {
+      Some(c) => Ok(OscChar(c)),
+      None() => Err(InvalidUtf8)
     }
   }
+
+  fn read_osc_color(&mut self) -> OscResult<OscType> {
+    let r = try_osc!(self.re.read_u8());
+    let g = try_osc!(self.re.read_u8());
+    let b = try_osc!(self.re.read_u8());
+    let a = try_osc!(self.re.read_u8());
+    Ok(OscColor(r, g, b, a))
+  }
+
+  fn read_osc_midi(&mut self) -> OscResult<OscType> {
+    let port = try_osc!(self.re.read_u8());
+    let status = try_osc!(self.re.read_u8());
+    let data1 = try_osc!(self.re.read_u8());
+    let data2 = try_osc!(self.re.read_u8());
+    Ok(OscMidi(port, status, data1, data2))
+  }
+}
+
+// Encodes a single argument to its own buffer so write_joined can
+// flush address, typetag string, and every argument in one shot instead
+// of one write call per argument.
+fn encode_osc_type(osc: &OscType) -> OscResult<~[u8]> {
+  let mut buf = OscBuf::new();
+  try_osc!(OscWriter::new(&mut buf).write(osc));
+  Ok(buf.unwrap())
+}
+
+/// Joins `parts` into a single buffer and writes it in one call.
+///
+/// This is NOT scatter/gather I/O -- `OscWrite` has no `IoVec`-style
+/// primitive, so there is no way to hand the pieces to the underlying
+/// writer without first concatenating them. What this buys is still
+/// useful on a datagram socket: one `write_bytes` call keeps a whole OSC
+/// message inside a single packet instead of fragmenting it across one
+/// write per field.
+fn write_joined<W: OscWrite>(wr: &mut W, parts: &[~[u8]]) -> OscResult<()> {
+  let mut buf: ~[u8] = ~[];
+  for part in parts.iter() {
+    buf.push_all(*part);
+  }
+  wr.write_bytes(buf)
 }
 
 // drawback, no specific method accesses
 pub fn get_type_tag(osc: &OscType) -> u8 {
   return match osc {
-    &OscString(_) => 's' as u8,
-    &OscInt(_)    => 'i' as u8,
-    &OscFloat(_)  => 'f' as u8,
-    &OscBlob(_)   => 'b' as u8
+    &OscString(_)  => 's' as u8,
+    &OscInt(_)     => 'i' as u8,
+    &OscFloat(_)   => 'f' as u8,
+    &OscBlob(_)    => 'b' as u8,
+    &OscInt64(_)   => 'h' as u8,
+    &OscDouble(_)  => 'd' as u8,
+    &OscTimeTag(_) => 't' as u8,
+    &OscChar(_)    => 'c' as u8,
+    &OscColor(..)  => 'r' as u8,
+    &OscMidi(..)   => 'm' as u8,
+    &OscTrue       => 'T' as u8,
+    &OscFalse      => 'F' as u8,
+    &OscNil        => 'N' as u8,
+    &OscInfinitum  => 'I' as u8
   };
 }
 
@@ -224,70 +619,406 @@ pub struct OscMessage {
 }
 
 impl OscMessage {
-  pub fn write_to(&self, outWriter: &mut Writer) -> IoResult<()> {
-    let mut out = OscWriter::new(outWriter);
-    unwrap_return_err!(out.write(&OscString(self.address.clone())));
+  /// Encodes the message and writes it out with a single underlying
+  /// `write_bytes` call rather than one call per address/typetags/argument.
+  pub fn write_vectored_to<W: OscWrite>(&self, outWriter: &mut W) -> OscResult<()> {
+    let mut parts: ~[~[u8]] = ~[];
+    parts.push(try_osc!(encode_osc_type(&OscString(self.address.clone()))));
+
     // typetag string is ",[isfb]*"
     let mut typetags = ~[',' as u8];
     for arg in self.arguments.iter() {
       typetags.push(get_type_tag(arg) as u8);
     }
-
-    let typetags_str = str::from_utf8_owned(typetags);
-    match typetags_str {
-      Some(tt) => unwrap_return_err!(out.write(&OscString(tt))),
-      None() => return Err(io::standard_error(InvalidInput))
-    }
+    let typetags_str = match owned_utf8(typetags) {
+      Some(tt) => tt,
+      None() => return Err(InvalidUtf8)
+    };
+    parts.push(try_osc!(encode_osc_type(&OscString(typetags_str))));
 
     for arg in self.arguments.iter() {
-      unwrap_return_err!(out.write(arg));
+      parts.push(try_osc!(encode_osc_type(arg)));
     }
 
-    Ok(())
+    write_joined(outWriter, parts)
   }
 
-  pub fn from_reader(re: &mut Reader) -> IoResult<~OscMessage> {
-    let mut reader = OscReader::new(re);
-    let address = match reader.read('s') {
-      Err(err) => return Err(err),
-      Ok(val) => match val {
+  pub fn write_to<W: OscWrite>(&self, outWriter: &mut W) -> OscResult<()> {
+    self.write_vectored_to(outWriter)
+  }
+
+  pub fn from_reader<R: OscRead>(re: &mut R) -> OscResult<~OscMessage> {
+    let address = {
+      let mut reader = OscReader::new(re);
+      match try_osc!(reader.read('s')) {
         OscString(valStr) => valStr,
-        _ => ~""
+        _ => return Err(MalformedAddress)
       }
     };
 
-    let typetags = match reader.read('s') {
-      Err(err) => return Err(err),
-      Ok(val) => match val {
-        OscString(valStr) => valStr,
-        _ => ~""
-      }
+    OscMessage::from_parts(address, re)
+  }
+
+  // Reads the typetag string and arguments for a message whose address
+  // has already been consumed from `re` -- shared by `from_reader` and
+  // `OscPacket::from_reader`, which has to peek the address first to
+  // tell a message from a bundle.
+  fn from_parts<R: OscRead>(address: ~str, re: &mut R) -> OscResult<~OscMessage> {
+    let mut reader = OscReader::new(re);
+    let typetags = match try_osc!(reader.read('s')) {
+      OscString(valStr) => valStr,
+      _ => return Err(MissingTypeTagString)
     };
 
-    println!("addr: {}", address);
-    println!("tt: {}", typetags);
-    let mut arguments: ~[OscType] = vec::with_capacity(typetags.len()-1);
+    if typetags.len() < 1 || typetags.as_bytes()[0] != ',' as u8 {
+      return Err(MissingTypeTagString);
+    }
+
+    let mut arguments: ~[OscType] = ~[];
     for typetag in typetags.slice_from(1).bytes() {
-      arguments.push(match reader.read(typetag as char) {
-        Ok(ot) => ot,
-        Err(err) => fail!("panicked on error: {}", err)
-      });
+      arguments.push(try_osc!(reader.read(typetag as char)));
     }
     return Ok(~OscMessage { address: address, arguments: arguments });
   }
 }
 
+/// 64-bit NTP timetag used to schedule bundle delivery: the high 32
+/// bits are seconds since 1900-01-01, the low 32 bits are the
+/// fractional second. `seconds = 0, fraction = 1` is the special
+/// "execute immediately" value.
+pub struct OscTimeTag {
+  seconds: u32,
+  fraction: u32
+}
+
+impl OscTimeTag {
+  pub fn immediately() -> OscTimeTag {
+    OscTimeTag { seconds: 0, fraction: 1 }
+  }
+
+  pub fn is_immediate(&self) -> bool {
+    self.seconds == 0 && self.fraction == 1
+  }
+
+  pub fn from_parts(seconds: u32, fraction: u32) -> OscTimeTag {
+    OscTimeTag { seconds: seconds, fraction: fraction }
+  }
+
+  pub fn to_parts(&self) -> (u32, u32) {
+    (self.seconds, self.fraction)
+  }
+
+  pub fn is_before(&self, other: &OscTimeTag) -> bool {
+    self.seconds < other.seconds ||
+      (self.seconds == other.seconds && self.fraction < other.fraction)
+  }
+
+  fn write_to<W: OscWrite>(&self, outWriter: &mut W) -> OscResult<()> {
+    try_osc!(write_be_u32(outWriter, self.seconds));
+    write_be_u32(outWriter, self.fraction)
+  }
+
+  fn from_reader<R: OscRead>(re: &mut R) -> OscResult<OscTimeTag> {
+    let seconds = try_osc!(read_be_u32(re));
+    let fraction = try_osc!(read_be_u32(re));
+    Ok(OscTimeTag::from_parts(seconds, fraction))
+  }
+}
+
+/// A decoded top-level OSC packet: either a single message or a bundle
+/// of further packets (messages or nested bundles) sharing a timetag.
+pub enum OscPacket {
+  Message(OscMessage),
+  Bundle(OscBundle)
+}
+
+pub struct OscBundle {
+  timetag: OscTimeTag,
+  elements: ~[OscPacket]
+}
+
+impl OscPacket {
+  pub fn from_reader<R: OscRead>(re: &mut R) -> OscResult<OscPacket> {
+    let first = {
+      let mut reader = OscReader::new(re);
+      match try_osc!(reader.read('s')) {
+        OscString(valStr) => valStr,
+        _ => return Err(MalformedAddress)
+      }
+    };
+
+    if first.as_slice() == "#bundle" {
+      let bundle = try_osc!(OscBundle::from_reader(re));
+      return Ok(Bundle(bundle));
+    }
+
+    if !first.starts_with("/") {
+      return Err(MalformedAddress);
+    }
+
+    let msg = try_osc!(OscMessage::from_parts(first, re));
+    Ok(Message(*msg))
+  }
+
+  pub fn write_to<W: OscWrite>(&self, outWriter: &mut W) -> OscResult<()> {
+    match self {
+      &Message(ref msg) => msg.write_to(outWriter),
+      &Bundle(ref bundle) => bundle.write_to(outWriter)
+    }
+  }
+}
+
+fn encode_packet(packet: &OscPacket) -> OscResult<~[u8]> {
+  let mut buf = OscBuf::new();
+  try_osc!(packet.write_to(&mut buf));
+  Ok(buf.unwrap())
+}
+
+impl OscBundle {
+  pub fn write_to<W: OscWrite>(&self, outWriter: &mut W) -> OscResult<()> {
+    try_osc!(OscWriter::new(outWriter).write(&OscString(~"#bundle")));
+    try_osc!(self.timetag.write_to(outWriter));
+
+    for element in self.elements.iter() {
+      let bytes = try_osc!(encode_packet(element));
+      try_osc!(write_be_i32(outWriter, bytes.len() as i32));
+      try_osc!(outWriter.write_bytes(bytes));
+    }
+
+    Ok(())
+  }
+
+  fn from_reader<R: OscRead>(re: &mut R) -> OscResult<OscBundle> {
+    let timetag = try_osc!(OscTimeTag::from_reader(re));
+
+    let mut elements: ~[OscPacket] = ~[];
+    loop {
+      let len = match read_be_i32(re) {
+        Ok(len) => len,
+        Err(TruncatedPacket) => break,
+        Err(err) => return Err(err)
+      };
+
+      if len < 0 {
+        return Err(TruncatedPacket);
+      }
+
+      let bytes = try_osc!(re.read_bytes(len as uint));
+      let mut elem_reader = OscSliceReader::new(bytes);
+      elements.push(try_osc!(OscPacket::from_reader(&mut elem_reader)));
+    }
+
+    Ok(OscBundle { timetag: timetag, elements: elements })
+  }
+}
+
+/// A user-supplied OSC method callback.
+///
+/// Handlers are stored as trait objects (rather than `proc`s, which can
+/// only be called once) so a dispatcher can hold many differently
+/// captured callbacks and invoke each one for every matching message.
+pub trait OscHandler {
+  fn handle(&self, msg: &OscMessage);
+}
+
+struct OscMethod {
+  pattern: ~str,
+  handler: ~OscHandler
+}
+
+/// Routes decoded packets to registered methods by matching the
+/// incoming message's address against each method's address pattern.
+pub struct OscDispatcher {
+  priv methods: ~[OscMethod]
+}
+
+impl OscDispatcher {
+  pub fn new() -> OscDispatcher {
+    OscDispatcher { methods: ~[] }
+  }
+
+  /// Registers `handler` to be invoked for every subsequently dispatched
+  /// message whose address matches `pattern` -- an exact address or an
+  /// OSC address pattern containing `?`, `*`, `[...]`, or `{...}`.
+  pub fn register(&mut self, pattern: &str, handler: ~OscHandler) {
+    self.methods.push(OscMethod { pattern: pattern.to_owned(), handler: handler });
+  }
+
+  /// Invokes every registered handler whose pattern matches `msg.address`.
+  pub fn dispatch_message(&self, msg: &OscMessage) {
+    for method in self.methods.iter() {
+      if address_matches(method.pattern, msg.address) {
+        method.handler.handle(msg);
+      }
+    }
+  }
+
+  /// Dispatches every message found in `packet`, recursing into nested
+  /// bundles. A bundle whose timetag is due (immediate, or not later
+  /// than `now`) has its elements dispatched immediately; a bundle
+  /// scheduled for the future is handed back instead of being dropped,
+  /// so the caller's event loop can re-offer it once its timetag has
+  /// elapsed.
+  pub fn dispatch(&self, packet: OscPacket, now: &OscTimeTag) -> ~[OscBundle] {
+    let mut pending: ~[OscBundle] = ~[];
+    self.dispatch_into(packet, now, &mut pending);
+    pending
+  }
+
+  fn dispatch_into(&self, packet: OscPacket, now: &OscTimeTag, pending: &mut ~[OscBundle]) {
+    match packet {
+      Message(msg) => self.dispatch_message(&msg),
+      Bundle(bundle) => {
+        if !bundle.timetag.is_immediate() && now.is_before(&bundle.timetag) {
+          pending.push(bundle);
+        } else {
+          for element in bundle.elements.move_iter() {
+            self.dispatch_into(element, now, pending);
+          }
+        }
+      }
+    }
+  }
+}
+
+// Matches an OSC address pattern (as registered with OscDispatcher)
+// against a concrete address from an incoming message. Patterns and
+// addresses are split into '/'-delimited segments, which must line up
+// one-to-one; each pair of segments is then matched independently.
+fn address_matches(pattern: &str, address: &str) -> bool {
+  let pattern_segments: ~[&str] = pattern.split('/').collect();
+  let address_segments: ~[&str] = address.split('/').collect();
+
+  if pattern_segments.len() != address_segments.len() {
+    return false;
+  }
+
+  for i in range(0, pattern_segments.len()) {
+    if !segment_matches(pattern_segments[i].as_bytes(), address_segments[i].as_bytes()) {
+      return false;
+    }
+  }
+
+  true
+}
+
+// Matches a single segment's pattern against a single segment of the
+// address, supporting '?', '*' (with backtracking), '[...]' character
+// classes, and '{...}' alternation.
+fn segment_matches(pat: &[u8], seg: &[u8]) -> bool {
+  if pat.len() == 0 {
+    return seg.len() == 0;
+  }
+
+  match pat[0] as char {
+    '*' => {
+      for i in range(0, seg.len() + 1) {
+        if segment_matches(pat.slice_from(1), seg.slice_from(i)) {
+          return true;
+        }
+      }
+      false
+    },
+    '?' => {
+      seg.len() > 0 && segment_matches(pat.slice_from(1), seg.slice_from(1))
+    },
+    '[' => {
+      match find_close(pat, ']' as u8) {
+        Some(close) => {
+          seg.len() > 0 &&
+            class_matches(pat.slice(1, close), seg[0] as char) &&
+            segment_matches(pat.slice_from(close + 1), seg.slice_from(1))
+        },
+        None() => false
+      }
+    },
+    '{' => {
+      match find_close(pat, '}' as u8) {
+        Some(close) => {
+          let rest = pat.slice_from(close + 1);
+          for alt in str::from_utf8(pat.slice(1, close)).unwrap_or("").split(',') {
+            let alt_bytes = alt.as_bytes();
+            if seg.len() >= alt_bytes.len() &&
+               seg.slice_to(alt_bytes.len()) == alt_bytes &&
+               segment_matches(rest, seg.slice_from(alt_bytes.len())) {
+              return true;
+            }
+          }
+          false
+        },
+        None() => false
+      }
+    },
+    c => {
+      seg.len() > 0 && seg[0] as char == c && segment_matches(pat.slice_from(1), seg.slice_from(1))
+    }
+  }
+}
+
+// Finds the index (within `pat`) of the `close` byte matching the
+// opening bracket at `pat[0]`. OSC address patterns don't nest `[...]`
+// or `{...}`, so a plain forward scan is enough.
+fn find_close(pat: &[u8], close: u8) -> Option<uint> {
+  let mut i = 1u;
+  while i < pat.len() {
+    if pat[i] == close {
+      return Some(i);
+    }
+    i += 1;
+  }
+  None()
+}
+
+// Matches a single char against a `[...]` character class body (with
+// the leading `[` and trailing `]` already stripped), supporting
+// `a-z`-style ranges and a leading `!` for negation.
+fn class_matches(body: &[u8], c: char) -> bool {
+  let (negate, body) = if body.len() > 0 && body[0] as char == '!' {
+    (true, body.slice_from(1))
+  } else {
+    (false, body)
+  };
+
+  let mut matched = false;
+  let mut i = 0u;
+  while i < body.len() {
+    if i + 2 < body.len() && body[i + 1] as char == '-' {
+      let lo = body[i] as char;
+      let hi = body[i + 2] as char;
+      if c >= lo && c <= hi {
+        matched = true;
+      }
+      i += 3;
+    } else {
+      if body[i] as char == c {
+        matched = true;
+      }
+      i += 1;
+    }
+  }
+
+  matched != negate
+}
+
 #[cfg(test)]
 mod test {
-  use std::io::{MemWriter, IoResult, MemReader};
-  use super::{OscType, OscMessage};
+  use std::cell::Cell;
+  use std::io::{MemWriter, MemReader};
+  use std::rc::Rc;
+  use super::{OscType, OscMessage, OscResult, OscString, OscInt, UnknownTypeTag};
+  use super::{OscPacket, OscBundle, OscTimeTag, Message, Bundle};
+  use super::{OscInt64, OscTrue, OscNil};
+  use super::{address_matches, OscDispatcher, OscHandler};
+  use super::{OscWriter, OscReader, OscBuf, OscSliceReader};
 
   #[test]
   fn test_write_osc_string() {
     let expected = (~"asdf\0\0\0\0").into_bytes();
     let mut writer = MemWriter::new();
-    let mut oscWriter = OscWriter::new(writer);
-    oscWriter.write(&OscString(~"asdf")).unwrap(); // fail if err returned
+    {
+      let mut oscWriter = OscWriter::new(&mut writer);
+      oscWriter.write(&OscString(~"asdf")).unwrap(); // fail if err returned
+    }
 
     assert_eq!(writer.unwrap(), expected);
   }
@@ -296,13 +1027,23 @@ mod test {
   fn test_read_osc_string() {
     let data = (~"asdf\0\0\0\0").into_bytes();
     let mut reader = MemReader::new(data);
-    let actual: IoResult<OscType> = reader.read_osc('s');
+    let actual = OscReader::new(&mut reader).read('s');
     match actual {
       Ok(OscString(val)) => assert_eq!(val, ~"asdf"),
       e => fail!("error: {}", e)
     }
   }
 
+  #[test]
+  fn test_read_unknown_type_tag() {
+    let data = ~[];
+    let mut reader = MemReader::new(data);
+    match OscReader::new(&mut reader).read('z') {
+      Err(UnknownTypeTag('z')) => {},
+      e => fail!("expected UnknownTypeTag, got: {}", e)
+    }
+  }
+
 /*
   #[test]
   fn test_write_osc_blob() {
@@ -378,15 +1119,120 @@ mod test {
   fn test_read_osc_message() {
     let data = (~"/test/do\0\0\0\0,ss\0Hello\0\0\0world\0\0\0").into_bytes();
     let mut reader = MemReader::new(data);
-    let actual: IoResult<~OscMessage> = OscMessage::from_reader(&mut reader);
+    let actual: OscResult<~OscMessage> = OscMessage::from_reader(&mut reader);
     match actual {
       Ok(msg) => {
         assert_eq!(msg.address, ~"/test/do");
         assert_eq!(msg.arguments.len(), 2);
-        println!("what0: {:?}", msg.arguments[0]);
-        println!("what1: {:?}", msg.arguments[1]);
       },
       e => fail!("error: {:?}", e)
     }
   }
+
+  #[test]
+  fn test_write_read_osc_bundle_roundtrip() {
+    let msg = OscMessage { address: ~"/test/do", arguments: ~[OscInt(4)] };
+    let bundle = OscBundle {
+      timetag: OscTimeTag::immediately(),
+      elements: ~[Message(msg)]
+    };
+    let packet = Bundle(bundle);
+
+    let mut writer = MemWriter::new();
+    packet.write_to(&mut writer).unwrap(); // fail if err
+
+    let mut reader = MemReader::new(writer.unwrap());
+    match OscPacket::from_reader(&mut reader) {
+      Ok(Bundle(b)) => {
+        assert!(b.timetag.is_immediate());
+        assert_eq!(b.elements.len(), 1);
+        match b.elements[0] {
+          Message(ref m) => assert_eq!(m.address, ~"/test/do"),
+          Bundle(_) => fail!("expected a nested message, got a bundle")
+        }
+      },
+      e => fail!("error: {:?}", e)
+    }
+  }
+
+  #[test]
+  fn test_write_read_osc_message_extended_types() {
+    let msg = OscMessage {
+      address: ~"/test/do",
+      arguments: ~[OscInt64(0x0011223344556677), OscTrue, OscNil]
+    };
+
+    let mut writer = MemWriter::new();
+    msg.write_to(&mut writer).unwrap(); // fail if err
+
+    let mut reader = MemReader::new(writer.unwrap());
+    match OscMessage::from_reader(&mut reader) {
+      Ok(actual) => {
+        assert_eq!(actual.arguments.len(), 3);
+        match actual.arguments[0] {
+          OscInt64(val) => assert_eq!(val, 0x0011223344556677),
+          ref other => fail!("expected OscInt64, got: {:?}", other)
+        }
+      },
+      e => fail!("error: {:?}", e)
+    }
+  }
+
+  #[test]
+  fn test_address_matches_wildcards() {
+    assert!(address_matches("/foo/bar", "/foo/bar"));
+    assert!(!address_matches("/foo/bar", "/foo/baz"));
+
+    assert!(address_matches("/foo/*", "/foo/bar"));
+    assert!(!address_matches("/foo/*", "/foo/bar/baz")); // segment counts differ
+
+    assert!(address_matches("/foo/b?r", "/foo/bar"));
+    assert!(!address_matches("/foo/b?r", "/foo/br"));
+
+    assert!(address_matches("/foo/[a-c]az", "/foo/baz"));
+    assert!(!address_matches("/foo/[a-c]az", "/foo/daz"));
+    assert!(address_matches("/foo/[!a-c]az", "/foo/daz"));
+
+    assert!(address_matches("/foo/{bar,baz}", "/foo/baz"));
+    assert!(!address_matches("/foo/{bar,baz}", "/foo/qux"));
+  }
+
+  #[test]
+  fn test_osc_buf_and_slice_reader_roundtrip() {
+    // Exercises the alloc-only, std-free encode/decode path used when
+    // the `std` feature is disabled.
+    let msg = OscMessage { address: ~"/test/do", arguments: ~[OscInt(4)] };
+
+    let mut buf = OscBuf::new();
+    msg.write_to(&mut buf).unwrap(); // fail if err
+    let bytes = buf.unwrap();
+
+    let mut reader = OscSliceReader::new(bytes);
+    match OscMessage::from_reader(&mut reader) {
+      Ok(actual) => assert_eq!(actual.address, ~"/test/do"),
+      e => fail!("error: {:?}", e)
+    }
+  }
+
+  struct CountingHandler {
+    count: Rc<Cell<uint>>
+  }
+
+  impl OscHandler for CountingHandler {
+    fn handle(&self, _msg: &OscMessage) {
+      self.count.set(self.count.get() + 1);
+    }
+  }
+
+  #[test]
+  fn test_dispatcher_invokes_matching_handler() {
+    let count = Rc::new(Cell::new(0u));
+    let mut dispatcher = OscDispatcher::new();
+    dispatcher.register("/foo/*", ~CountingHandler { count: count.clone() } as ~OscHandler);
+
+    dispatcher.dispatch_message(&OscMessage { address: ~"/foo/bar", arguments: ~[] });
+    dispatcher.dispatch_message(&OscMessage { address: ~"/other", arguments: ~[] });
+
+    assert_eq!(count.get(), 1);
+  }
 }